@@ -0,0 +1,1125 @@
+use std::{
+  convert::{
+    self,
+    TryInto,
+  },
+  default,
+  fmt,
+  io::{
+    Read,
+    Seek,
+    SeekFrom,
+    Write,
+  },
+  mem,
+  str,
+  time::{
+    SystemTime,
+    UNIX_EPOCH,
+  },
+};
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub trait ReadWriteSeek: Read + Write + Seek {}
+impl<T: Read + Write + Seek> ReadWriteSeek for T {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+  Fat12,
+  Fat16,
+  Fat32,
+}
+
+pub fn classify_fat_type(header: &FATHeader, fat_size: u32) -> FatType {
+  // table_size_16 == 0 is the same signal open_at() uses to decide whether a
+  // FAT32Ext follows the BPB, so fat_type must agree with it: a volume that
+  // open_at() parsed as FAT32 can never be classified as FAT12/16 here, no
+  // matter what the cluster-count heuristic below would say.
+  if header.table_size_16 == 0 {
+    return FatType::Fat32;
+  }
+
+  let root_dir_sectors = (((header.root_entry_count as u32) * 32)
+    + ((header.bytes_per_sector as u32) - 1)) / (header.bytes_per_sector as u32);
+  let total_sectors = if header.total_sectors_16 != 0 {
+    header.total_sectors_16 as u32
+  } else {
+    header.total_sectors_32
+  };
+  let data_sectors = total_sectors
+    - ((header.reserved_sector_count as u32) + (header.table_count as u32) * fat_size + root_dir_sectors);
+  let cluster_count = data_sectors / (header.sectors_per_cluster as u32);
+
+  if cluster_count < 4085 {
+    FatType::Fat12
+  } else {
+    FatType::Fat16
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatValue {
+  Free,
+  Bad,
+  EndOfChain,
+  Next(u32),
+}
+
+pub fn read_fat_entry<T: ReadSeek>(storage: &mut T, fat_start: u64, fat_type: FatType, cluster: u32) -> FatValue {
+  match fat_type {
+    FatType::Fat32 => {
+      let offset = fat_start + (cluster as u64) * 4;
+      storage.seek(SeekFrom::Start(offset)).unwrap();
+      let mut buf = [0u8; 4];
+      storage.read_exact(&mut buf).unwrap();
+      let raw = u32::from_le_bytes(buf) & 0x0FFFFFFF;
+      match raw {
+        0x00000000 => FatValue::Free,
+        0x0FFFFFF7 => FatValue::Bad,
+        v if v >= 0x0FFFFFF8 => FatValue::EndOfChain,
+        v => FatValue::Next(v),
+      }
+    }
+    FatType::Fat16 => {
+      let offset = fat_start + (cluster as u64) * 2;
+      storage.seek(SeekFrom::Start(offset)).unwrap();
+      let mut buf = [0u8; 2];
+      storage.read_exact(&mut buf).unwrap();
+      let raw = u16::from_le_bytes(buf) as u32;
+      match raw {
+        0x0000 => FatValue::Free,
+        0xFFF7 => FatValue::Bad,
+        v if v >= 0xFFF8 => FatValue::EndOfChain,
+        v => FatValue::Next(v),
+      }
+    }
+    FatType::Fat12 => {
+      let offset = fat_start + (cluster as u64) * 3 / 2;
+      storage.seek(SeekFrom::Start(offset)).unwrap();
+      let mut buf = [0u8; 2];
+      storage.read_exact(&mut buf).unwrap();
+      let packed = u16::from_le_bytes(buf) as u32;
+      let raw = if cluster % 2 == 0 { packed & 0x0FFF } else { packed >> 4 };
+      match raw {
+        0x000 => FatValue::Free,
+        0xFF7 => FatValue::Bad,
+        v if v >= 0xFF8 => FatValue::EndOfChain,
+        v => FatValue::Next(v),
+      }
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct Fat {
+  fat_start: u64,
+  root_dir_start: u64,
+  data_start: u64,
+  bytes_per_sector: u16,
+  sectors_per_cluster: u8,
+  fat_type: FatType,
+}
+
+impl Fat {
+  fn cluster_offset(self: &Self, cluster: u32) -> u64 {
+    self.data_start + ((cluster - 2) as u64) * (self.sectors_per_cluster as u64) * (self.bytes_per_sector as u64)
+  }
+
+  fn cluster_size(self: &Self) -> usize {
+    (self.sectors_per_cluster as usize) * (self.bytes_per_sector as usize)
+  }
+
+  pub fn chain<'a, T: ReadSeek>(self: &'a Self, storage: &'a mut T, start_cluster: u32) -> ClusterChain<'a, T> {
+    // Cluster 0 (and the reserved cluster 1) mean "no data allocated" -
+    // most commonly a zero-length file, or a FAT12/16 ".." entry that
+    // points at the root directory. Neither has a cluster_offset(), so
+    // don't even start iterating.
+    let next_cluster = if start_cluster < 2 { None } else { Some(start_cluster) };
+
+    ClusterChain {
+      fat: self,
+      storage,
+      next_cluster,
+    }
+  }
+
+  pub fn read_file<T: ReadSeek>(self: &Self, storage: &mut T, start_cluster: u32, size: u32) -> Vec<u8> {
+    let mut contents = vec![];
+    for cluster in self.chain(storage, start_cluster) {
+      contents.extend_from_slice(&cluster);
+    }
+    contents.truncate(size as usize);
+    contents
+  }
+}
+
+pub struct ClusterChain<'a, T> {
+  fat: &'a Fat,
+  storage: &'a mut T,
+  next_cluster: Option<u32>,
+}
+
+impl<'a, T: ReadSeek> Iterator for ClusterChain<'a, T> {
+  type Item = Vec<u8>;
+
+  fn next(self: &mut Self) -> Option<Self::Item> {
+    let cluster = self.next_cluster?;
+
+    let offset = self.fat.cluster_offset(cluster);
+    let mut buffer = vec![0u8; self.fat.cluster_size()];
+    self.storage.seek(SeekFrom::Start(offset)).unwrap();
+    self.storage.read_exact(&mut buffer).unwrap();
+
+    self.next_cluster = match read_fat_entry(self.storage, self.fat.fat_start, self.fat.fat_type, cluster) {
+      FatValue::Next(n) => Some(n),
+      FatValue::Free | FatValue::Bad | FatValue::EndOfChain => None,
+    };
+
+    Some(buffer)
+  }
+}
+
+#[derive(Debug)]
+pub struct PartitionEntry {
+  status: u8,
+  partition_type: u8,
+  lba_start: u32,
+  sector_count: u32,
+}
+
+impl convert::From<[u8; 16]> for PartitionEntry {
+  fn from(target: [u8; 16]) -> Self {
+    PartitionEntry {
+      status: target[0],
+      partition_type: target[4],
+      lba_start: u32::from_le_bytes(target[8..12].try_into().unwrap()),
+      sector_count: u32::from_le_bytes(target[12..16].try_into().unwrap()),
+    }
+  }
+}
+
+impl PartitionEntry {
+  fn is_fat(self: &Self) -> bool {
+    matches!(self.partition_type, 0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E | 0x0F)
+  }
+}
+
+#[derive(Debug)]
+pub struct Mbr {
+  partitions: [PartitionEntry; 4],
+}
+
+fn looks_like_fat_vbr(sector: &[u8; 512]) -> bool {
+  let header_buf: [u8; FATHEADER_SIZE] = sector[0..FATHEADER_SIZE].try_into().unwrap();
+  let header = FATHeader::from(header_buf);
+
+  let bootjmp_ok = header.bootjmp[0] == 0xEB || header.bootjmp[0] == 0xE9;
+  let bytes_per_sector_ok = matches!(header.bytes_per_sector, 512 | 1024 | 2048 | 4096);
+  let sectors_per_cluster_ok = header.sectors_per_cluster.is_power_of_two();
+  let reserved_sector_count_ok = header.reserved_sector_count != 0;
+  let table_count_ok = header.table_count == 1 || header.table_count == 2;
+  let media_type_ok = header.media_type >= 0xF0;
+
+  bootjmp_ok && bytes_per_sector_ok && sectors_per_cluster_ok && reserved_sector_count_ok
+    && table_count_ok && media_type_ok
+}
+
+pub fn read_mbr<T: ReadSeek>(storage: &mut T) -> Option<Mbr> {
+  storage.seek(SeekFrom::Start(0)).unwrap();
+  let mut sector = [0u8; 512];
+  storage.read_exact(&mut sector).unwrap();
+
+  if sector[510] != 0x55 || sector[511] != 0xAA {
+    return None;
+  }
+
+  // A bare FAT volume (no partition table) ends in 0x55AA too, at the same
+  // offset as an MBR signature, so the signature alone can't tell them apart.
+  // Bail out to "no MBR" if the sector also parses as a plausible FAT BPB.
+  if looks_like_fat_vbr(&sector) {
+    return None;
+  }
+
+  let mut entries: [[u8; 16]; 4] = [[0; 16]; 4];
+  for i in 0..4 {
+    let offset = 446 + i * 16;
+    entries[i] = sector[offset..offset + 16].try_into().unwrap();
+  }
+
+  Some(Mbr {
+    partitions: [
+      PartitionEntry::from(entries[0]),
+      PartitionEntry::from(entries[1]),
+      PartitionEntry::from(entries[2]),
+      PartitionEntry::from(entries[3]),
+    ],
+  })
+}
+
+pub fn select_partition_offset(mbr: &Mbr, partition_index: usize) -> u64 {
+  let partition = &mbr.partitions[partition_index];
+  if !partition.is_fat() {
+    panic!(
+      "partition {} is not a FAT partition (type 0x{:02X})",
+      partition_index, partition.partition_type,
+    );
+  }
+  (partition.lba_start as u64) * 512
+}
+
+#[derive(Debug)]
+pub struct Standard8Point3Format {
+  filename: [u8; 11],
+  attributes: u8,
+  reserved1: u8,
+  created_tenths_seconds: u8,
+  created_time: [u8; 2],
+  created_date: [u8; 2],
+  last_access_date: [u8; 2],
+  highbits_cluster_number: [u8; 2],
+  last_update_time: [u8; 2],
+  last_update_date: [u8; 2],
+  lowbits_cluster_number: [u8; 2],
+  filesize: [u8; 4],
+}
+
+impl Standard8Point3Format {
+  fn short_name(self: &Self) -> String {
+    let base = str::from_utf8(&self.filename[0..8]).unwrap_or("").trim_end();
+    let ext = str::from_utf8(&self.filename[8..11]).unwrap_or("").trim_end();
+
+    if ext.is_empty() {
+      base.to_string()
+    } else {
+      format!("{}.{}", base, ext)
+    }
+  }
+}
+
+impl convert::From<[u8; 32]> for Standard8Point3Format {
+  fn from(target: [u8; 32]) -> Self {
+    Standard8Point3Format {
+      filename: target[0..=10].try_into().unwrap(),
+      attributes: target[11],
+      reserved1: target[12],
+      created_tenths_seconds: target[13],
+      created_time: target[14..=15].try_into().unwrap(),
+      created_date: target[16..=17].try_into().unwrap(),
+      last_access_date: target[18..=19].try_into().unwrap(),
+      highbits_cluster_number: target[20..=21].try_into().unwrap(),
+      last_update_time: target[22..=23].try_into().unwrap(),
+      last_update_date: target[24..=25].try_into().unwrap(),
+      lowbits_cluster_number: target[26..=27].try_into().unwrap(),
+      filesize: target[28..=31].try_into().unwrap(),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct LongFileName {
+  order: u8,
+  first5chars: [u8; 10],
+  attribute: u8,
+  entry_type: u8,
+  checksum: u8,
+  next6chars: [u8; 12],
+  zeros: [u8; 2],
+  final2chars: [u8; 4],
+}
+
+impl convert::From<[u8; 32]> for LongFileName {
+  fn from(target: [u8; 32]) -> Self {
+    LongFileName {
+      order: target[0],
+      first5chars: target[1..=10].try_into().unwrap(),
+      attribute: target[11],
+      entry_type: target[12],
+      checksum: target[13],
+      next6chars: target[14..=25].try_into().unwrap(),
+      zeros: target[26..=27].try_into().unwrap(),
+      final2chars: target[28..=31].try_into().unwrap(),
+    }
+  }
+}
+
+impl LongFileName {
+  fn sequence_number(self: &Self) -> u8 {
+    self.order & 0x3F
+  }
+
+  fn is_last(self: &Self) -> bool {
+    self.order & 0x40 != 0
+  }
+
+  fn code_units(self: &Self) -> Vec<u16> {
+    let mut units = vec![];
+    for chunk in self.first5chars.chunks_exact(2) {
+      units.push(u16::from_le_bytes([chunk[0], chunk[1]]));
+    }
+    for chunk in self.next6chars.chunks_exact(2) {
+      units.push(u16::from_le_bytes([chunk[0], chunk[1]]));
+    }
+    for chunk in self.final2chars.chunks_exact(2) {
+      units.push(u16::from_le_bytes([chunk[0], chunk[1]]));
+    }
+    units
+  }
+}
+
+pub fn dos_checksum(filename: &[u8; 11]) -> u8 {
+  let mut sum: u8 = 0;
+  for &byte in filename.iter() {
+    sum = ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(byte);
+  }
+  sum
+}
+
+#[derive(Debug)]
+pub struct DirEntry {
+  long_name: Vec<LongFileName>,
+  meta: Standard8Point3Format,
+}
+
+impl default::Default for DirEntry {
+  fn default() -> Self {
+    DirEntry {
+      long_name: vec![],
+      meta: Standard8Point3Format {
+        filename: [0,0,0,0,0,0,0,0,0,0,0],
+        attributes: 0,
+        reserved1: 0,
+        created_tenths_seconds: 0,
+        created_time: [0,0],
+        created_date: [0,0],
+        last_access_date: [0,0],
+        highbits_cluster_number: [0,0],
+        last_update_time: [0,0],
+        last_update_date: [0,0],
+        lowbits_cluster_number: [0,0],
+        filesize: [0,0,0,0],
+      },
+    }
+  }
+}
+
+pub fn datetime(date: [u8; 2], time: [u8; 2]) -> String {
+  let seconds = time[0] & 0x1F;
+  let minutes = ((time[1] & 0x07) << 3) | ((time[0] & 0xE0) >> 5);
+  let hours = (time[1] & 0xF8) >> 3;
+
+  let day = date[0] & 0x1F;
+  let month = ((date[1] & 0x01) << 3) | ((date[0] & 0xE0) >> 5);
+  let year = ((date[1] & 0xFE) >> 1) as u16 + 1980;
+  let datetime_str = format!("{}-{}-{}T{}:{}:{}", year, month, day, hours, minutes, seconds);
+  datetime_str.to_string()
+}
+
+const ATTR_DIRECTORY: u8 = 0x10;
+
+impl DirEntry {
+  pub fn name(self: &Self) -> String {
+    if self.long_name.is_empty() {
+      return self.meta.short_name();
+    }
+
+    let mut slots: Vec<&LongFileName> = self.long_name.iter().collect();
+    slots.sort_by_key(|slot| slot.sequence_number());
+
+    let checksum = dos_checksum(&self.meta.filename);
+    let checksums_match = slots.iter().all(|slot| slot.checksum == checksum);
+    let last_slot_flagged = slots.last().map_or(false, |slot| slot.is_last());
+
+    if !checksums_match || !last_slot_flagged {
+      return self.meta.short_name();
+    }
+
+    let mut code_units = vec![];
+    'slots: for slot in slots {
+      for unit in slot.code_units() {
+        if unit == 0x0000 { break 'slots; }
+        if unit == 0xFFFF { continue; }
+        code_units.push(unit);
+      }
+    }
+
+    String::from_utf16_lossy(&code_units)
+  }
+  pub fn cluster(self: &Self) -> u32 {
+    let hi = self.meta.highbits_cluster_number;
+    let lo = self.meta.lowbits_cluster_number;
+
+    let cluster_number =
+        ((hi[1] as u32) << 24)
+      | ((hi[0] as u32) << 16)
+      | ((lo[1] as u32) << 8)
+      | ((lo[0] as u32) << 0);
+
+    cluster_number
+  }
+  pub fn size(self: &Self) -> u32 {
+    let filesize = self.meta.filesize;
+    let size =
+        ((filesize[3] as u32) << 24)
+      | ((filesize[2] as u32) << 16)
+      | ((filesize[1] as u32) << 8)
+      | ((filesize[0] as u32) << 0);
+
+    size
+  }
+  pub fn is_dir(self: &Self) -> bool {
+    self.meta.attributes & ATTR_DIRECTORY != 0
+  }
+  pub fn created_at(self: &Self) -> String {
+    datetime(self.meta.created_date, self.meta.created_time)
+  }
+}
+
+#[derive(Debug)]
+pub struct FATHeader {
+	bootjmp: [u8; 3],
+	oem_name: [u8; 8],
+	bytes_per_sector: u16,
+	sectors_per_cluster: u8,
+	reserved_sector_count: u16,
+	table_count: u8,
+	root_entry_count: u16,
+	total_sectors_16: u16,
+	media_type: u8,
+	table_size_16: u16,
+	sectors_per_track: u16,
+	head_side_count: u16,
+	hidden_sector_count: u32,
+	total_sectors_32: u32,
+}
+
+const FATHEADER_SIZE:usize = mem::size_of::<FATHeader>();
+
+#[derive(Debug)]
+pub struct FAT32Ext {
+	table_size_32: u32,
+	extended_flags: u16,
+	fat_version: u16,
+	root_cluster: u32,
+	fat_info: u16,
+	backup_bs_sector: u16,
+	reserved_0: [u8; 12],
+	drive_number: u8,
+	reserved_1: u8,
+	boot_signature: u8,
+	volume_id: u32,
+	volume_label: [u8; 11],
+	fat_type_label: [u8; 8],
+}
+
+const FAT32EXT_SIZE:usize = mem::size_of::<FAT32Ext>();
+
+impl From<[u8; FATHEADER_SIZE]> for FATHeader {
+  fn from(target: [u8; FATHEADER_SIZE]) -> Self {
+    FATHeader {
+    	bootjmp: target[0..3].try_into().unwrap(),
+    	oem_name: target[3..11].try_into().unwrap(),
+    	bytes_per_sector: ((target[12] as u16) << 8) | (target[11] as u16),
+    	sectors_per_cluster: target[13],
+    	reserved_sector_count: ((target[15] as u16) << 8) | (target[14] as u16),
+    	table_count: target[16],
+    	root_entry_count: ((target[18] as u16) << 8) | (target[17] as u16),
+    	total_sectors_16: ((target[20] as u16) << 8) | (target[19] as u16),
+    	media_type: target[21],
+    	table_size_16: ((target[23] as u16) << 8) | (target[22] as u16),
+    	sectors_per_track: ((target[25] as u16) << 8) | (target[24] as u16),
+    	head_side_count: ((target[27] as u16) << 8) | (target[26] as u16),
+    	hidden_sector_count: ((target[31] as u32) << 24) | ((target[30] as u32) << 16)
+        | ((target[29] as u32) << 8) | (target[28] as u32),
+    	total_sectors_32: ((target[35] as u32) << 24) | ((target[34] as u32) << 16)
+        | ((target[33] as u32) << 8) | (target[32] as u32),
+    }
+  }
+}
+
+impl FATHeader {
+  fn to_bytes(self: &Self) -> [u8; FATHEADER_SIZE] {
+    let mut buf = [0u8; FATHEADER_SIZE];
+    buf[0..3].copy_from_slice(&self.bootjmp);
+    buf[3..11].copy_from_slice(&self.oem_name);
+    buf[11..13].copy_from_slice(&self.bytes_per_sector.to_le_bytes());
+    buf[13] = self.sectors_per_cluster;
+    buf[14..16].copy_from_slice(&self.reserved_sector_count.to_le_bytes());
+    buf[16] = self.table_count;
+    buf[17..19].copy_from_slice(&self.root_entry_count.to_le_bytes());
+    buf[19..21].copy_from_slice(&self.total_sectors_16.to_le_bytes());
+    buf[21] = self.media_type;
+    buf[22..24].copy_from_slice(&self.table_size_16.to_le_bytes());
+    buf[24..26].copy_from_slice(&self.sectors_per_track.to_le_bytes());
+    buf[26..28].copy_from_slice(&self.head_side_count.to_le_bytes());
+    buf[28..32].copy_from_slice(&self.hidden_sector_count.to_le_bytes());
+    buf[32..36].copy_from_slice(&self.total_sectors_32.to_le_bytes());
+    buf
+  }
+}
+
+impl fmt::Display for FATHeader {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self)
+  }
+}
+
+
+impl From<[u8; FAT32EXT_SIZE]> for FAT32Ext {
+  fn from(target: [u8; FAT32EXT_SIZE]) -> Self {
+    FAT32Ext {
+    	table_size_32: ((target[3] as u32) << 24) | ((target[2] as u32) << 16)
+    	  | ((target[1] as u32) << 8) | (target[0] as u32),
+    	extended_flags: ((target[5] as u16) << 8) | (target[4] as u16),
+    	fat_version: ((target[7] as u16) << 8) | (target[6] as u16),
+    	root_cluster: ((target[11] as u32) << 24) | ((target[10] as u32) << 16)
+    	  | ((target[9] as u32) << 8) | (target[8] as u32),
+    	fat_info: ((target[13] as u16) << 8) | (target[12] as u16),
+    	backup_bs_sector: ((target[15] as u16) << 8) | (target[14] as u16),
+    	reserved_0: [0; 12],
+    	drive_number: target[28],
+    	reserved_1: target[29],
+    	boot_signature: target[30],
+    	volume_id: ((target[34] as u32) << 24) | ((target[33] as u32) << 16)
+    	  | ((target[32] as u32) << 8) | (target[31] as u32),
+    	volume_label: target[35..46].try_into().unwrap(),
+    	fat_type_label: target[46..54].try_into().unwrap(),
+    }
+  }
+}
+
+impl FAT32Ext {
+  fn to_bytes(self: &Self) -> [u8; FAT32EXT_SIZE] {
+    let mut buf = [0u8; FAT32EXT_SIZE];
+    buf[0..4].copy_from_slice(&self.table_size_32.to_le_bytes());
+    buf[4..6].copy_from_slice(&self.extended_flags.to_le_bytes());
+    buf[6..8].copy_from_slice(&self.fat_version.to_le_bytes());
+    buf[8..12].copy_from_slice(&self.root_cluster.to_le_bytes());
+    buf[12..14].copy_from_slice(&self.fat_info.to_le_bytes());
+    buf[14..16].copy_from_slice(&self.backup_bs_sector.to_le_bytes());
+    buf[16..28].copy_from_slice(&self.reserved_0);
+    buf[28] = self.drive_number;
+    buf[29] = self.reserved_1;
+    buf[30] = self.boot_signature;
+    buf[31..35].copy_from_slice(&self.volume_id.to_le_bytes());
+    buf[35..46].copy_from_slice(&self.volume_label);
+    buf[46..54].copy_from_slice(&self.fat_type_label);
+    buf
+  }
+}
+
+impl fmt::Display for FAT32Ext {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self)
+  }
+}
+
+#[derive(Debug)]
+struct FsInfo {
+  lead_signature: u32,
+  struct_signature: u32,
+  free_cluster_count: u32,
+  next_free_cluster: u32,
+  trail_signature: u32,
+}
+
+impl FsInfo {
+  fn to_bytes(self: &Self) -> [u8; 512] {
+    let mut buf = [0u8; 512];
+    buf[0..4].copy_from_slice(&self.lead_signature.to_le_bytes());
+    buf[484..488].copy_from_slice(&self.struct_signature.to_le_bytes());
+    buf[488..492].copy_from_slice(&self.free_cluster_count.to_le_bytes());
+    buf[492..496].copy_from_slice(&self.next_free_cluster.to_le_bytes());
+    buf[508..512].copy_from_slice(&self.trail_signature.to_le_bytes());
+    buf
+  }
+}
+
+pub fn format_fat32<T: ReadWriteSeek>(storage: &mut T, size_bytes: u64) {
+  let bytes_per_sector: u16 = 512;
+  let sectors_per_cluster: u8 = 8;
+  let reserved_sector_count: u16 = 32;
+  let table_count: u8 = 2;
+
+  let total_sectors_32 = (size_bytes / (bytes_per_sector as u64)) as u32;
+
+  let tmp1 = total_sectors_32 - (reserved_sector_count as u32);
+  let tmp2 = ((256 * (sectors_per_cluster as u32)) + (table_count as u32)) / 2;
+  let table_size_32 = (tmp1 + (tmp2 - 1)) / tmp2;
+
+  let volume_id = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap()
+    .as_secs() as u32;
+
+  let fat_header = FATHeader {
+    bootjmp: [0xEB, 0x58, 0x90],
+    oem_name: *b"MSWIN4.1",
+    bytes_per_sector,
+    sectors_per_cluster,
+    reserved_sector_count,
+    table_count,
+    root_entry_count: 0,
+    total_sectors_16: 0,
+    media_type: 0xF8,
+    table_size_16: 0,
+    sectors_per_track: 0,
+    head_side_count: 0,
+    hidden_sector_count: 0,
+    total_sectors_32,
+  };
+
+  let fat32_ext = FAT32Ext {
+    table_size_32,
+    extended_flags: 0,
+    fat_version: 0,
+    root_cluster: 2,
+    fat_info: 1,
+    backup_bs_sector: 6,
+    reserved_0: [0; 12],
+    drive_number: 0x80,
+    reserved_1: 0,
+    boot_signature: 0x29,
+    volume_id,
+    volume_label: *b"NO NAME    ",
+    fat_type_label: *b"FAT32   ",
+  };
+
+  let fs_info = FsInfo {
+    lead_signature: 0x41615252,
+    struct_signature: 0x61417272,
+    free_cluster_count: 0xFFFFFFFF,
+    next_free_cluster: 0xFFFFFFFF,
+    trail_signature: 0xAA550000,
+  };
+
+  let mut boot_sector = [0u8; 512];
+  boot_sector[0..FATHEADER_SIZE].copy_from_slice(&fat_header.to_bytes());
+  boot_sector[FATHEADER_SIZE..FATHEADER_SIZE + FAT32EXT_SIZE].copy_from_slice(&fat32_ext.to_bytes());
+  boot_sector[510] = 0x55;
+  boot_sector[511] = 0xAA;
+
+  storage.seek(SeekFrom::Start(0)).unwrap();
+  storage.write_all(&boot_sector).unwrap();
+
+  storage.seek(SeekFrom::Start((fat32_ext.fat_info as u64) * (bytes_per_sector as u64))).unwrap();
+  storage.write_all(&fs_info.to_bytes()).unwrap();
+
+  storage.seek(SeekFrom::Start((fat32_ext.backup_bs_sector as u64) * (bytes_per_sector as u64))).unwrap();
+  storage.write_all(&boot_sector).unwrap();
+
+  let fat_start = (reserved_sector_count as u64) * (bytes_per_sector as u64);
+  let fat_bytes = (table_size_32 as u64) * (bytes_per_sector as u64);
+
+  let mut fat = vec![0u8; fat_bytes as usize];
+  fat[0..4].copy_from_slice(&0x0FFFFFF8u32.to_le_bytes());
+  fat[4..8].copy_from_slice(&0x0FFFFFFFu32.to_le_bytes());
+  fat[8..12].copy_from_slice(&0x0FFFFFFFu32.to_le_bytes());
+
+  for table in 0..(table_count as u64) {
+    storage.seek(SeekFrom::Start(fat_start + table * fat_bytes)).unwrap();
+    storage.write_all(&fat).unwrap();
+  }
+
+  let data_start = fat_start + (table_count as u64) * fat_bytes;
+  let root_cluster_bytes = vec![0u8; (sectors_per_cluster as usize) * (bytes_per_sector as usize)];
+  storage.seek(SeekFrom::Start(data_start)).unwrap();
+  storage.write_all(&root_cluster_bytes).unwrap();
+}
+
+
+enum DirLocation {
+  FixedRegion { start: u64, entry_count: u16 },
+  Cluster(u32),
+}
+
+pub struct VolumeManager<T> {
+  storage: T,
+  fat: Fat,
+  fat_type: FatType,
+  root_cluster: u32,
+  root_entry_count: u16,
+}
+
+impl<T: ReadSeek> VolumeManager<T> {
+  pub fn open(storage: T) -> Self {
+    Self::open_at(storage, 0)
+  }
+
+  pub fn open_at(mut storage: T, base_offset: u64) -> Self {
+    storage.seek(SeekFrom::Start(base_offset)).unwrap();
+    let mut fat_header_buf: [u8; FATHEADER_SIZE] = [0; FATHEADER_SIZE];
+    storage.read_exact(&mut fat_header_buf).unwrap();
+    let fat_header = FATHeader::from(fat_header_buf);
+
+    let (fat_size, root_cluster) = if fat_header.table_size_16 != 0 {
+      (fat_header.table_size_16 as u32, 0)
+    } else {
+      let mut fat32_ext_buf: [u8; FAT32EXT_SIZE] = [0; FAT32EXT_SIZE];
+      storage.read_exact(&mut fat32_ext_buf).unwrap();
+      let fat32_ext = FAT32Ext::from(fat32_ext_buf);
+      (fat32_ext.table_size_32, fat32_ext.root_cluster)
+    };
+
+    let fat_type = classify_fat_type(&fat_header, fat_size);
+
+    let root_dir_sectors = (((fat_header.root_entry_count as u32) * 32)
+      + ((fat_header.bytes_per_sector as u32) - 1)) / (fat_header.bytes_per_sector as u32);
+    let fat_start = base_offset + (fat_header.reserved_sector_count as u64) * (fat_header.bytes_per_sector as u64);
+    let root_dir_start = base_offset + ((fat_header.reserved_sector_count as u64)
+      + (fat_header.table_count as u64) * (fat_size as u64))
+      * (fat_header.bytes_per_sector as u64);
+    let data_start = root_dir_start + (root_dir_sectors as u64) * (fat_header.bytes_per_sector as u64);
+
+    let fat = Fat {
+      fat_start,
+      root_dir_start,
+      data_start,
+      bytes_per_sector: fat_header.bytes_per_sector,
+      sectors_per_cluster: fat_header.sectors_per_cluster,
+      fat_type,
+    };
+
+    VolumeManager {
+      storage,
+      fat,
+      fat_type,
+      root_cluster,
+      root_entry_count: fat_header.root_entry_count,
+    }
+  }
+
+  pub fn root_dir(self: &mut Self) -> Vec<DirEntry> {
+    match self.fat_type {
+      FatType::Fat32 => self.read_directory(DirLocation::Cluster(self.root_cluster)),
+      FatType::Fat12 | FatType::Fat16 => self.read_directory(DirLocation::FixedRegion {
+        start: self.fat.root_dir_start,
+        entry_count: self.root_entry_count,
+      }),
+    }
+  }
+
+  pub fn sub_dir(self: &mut Self, entry: &DirEntry) -> Vec<DirEntry> {
+    self.read_directory(DirLocation::Cluster(entry.cluster()))
+  }
+
+  pub fn read_file(self: &mut Self, entry: &DirEntry) -> Vec<u8> {
+    self.fat.read_file(&mut self.storage, entry.cluster(), entry.size())
+  }
+
+  fn read_directory(self: &mut Self, location: DirLocation) -> Vec<DirEntry> {
+    let bytes = match location {
+      DirLocation::FixedRegion { start, entry_count } => {
+        self.storage.seek(SeekFrom::Start(start)).unwrap();
+        let mut buf = vec![0u8; (entry_count as usize) * 32];
+        self.storage.read_exact(&mut buf).unwrap();
+        buf
+      }
+      DirLocation::Cluster(cluster) => {
+        let mut contents = vec![];
+        for block in self.fat.chain(&mut self.storage, cluster) {
+          contents.extend_from_slice(&block);
+        }
+        contents
+      }
+    };
+
+    let mut entries = vec![];
+    let mut current = DirEntry::default();
+    for chunk in bytes.chunks_exact(32) {
+      if chunk[0] == 0x00 { break; }
+      if chunk[0] == 0xE5 { continue; }
+
+      let record: [u8; 32] = chunk.try_into().unwrap();
+      if chunk[11] == 0x0F {
+        current.long_name.push(LongFileName::from(record));
+      } else {
+        current.meta = Standard8Point3Format::from(record);
+        entries.push(current);
+        current = DirEntry::default();
+      }
+    }
+
+    entries
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  fn formatted_image(size_bytes: u64) -> Cursor<Vec<u8>> {
+    let mut storage = Cursor::new(vec![0u8; size_bytes as usize]);
+    format_fat32(&mut storage, size_bytes);
+    storage
+  }
+
+  // Mirrors the on-disk layout LongFileName::from() expects: 13 UTF-16 code
+  // units per slot, terminated with 0x0000 and padded with 0xFFFF.
+  fn build_lfn_slots(name: &str, checksum: u8) -> Vec<[u8; 32]> {
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    units.push(0x0000);
+    while !units.len().is_multiple_of(13) {
+      units.push(0xFFFF);
+    }
+
+    let slot_count = units.len() / 13;
+    (0..slot_count).map(|slot_index| {
+      let chunk = &units[slot_index * 13..slot_index * 13 + 13];
+      let mut order = (slot_index + 1) as u8;
+      if slot_index == slot_count - 1 {
+        order |= 0x40;
+      }
+
+      let mut buf = [0u8; 32];
+      buf[0] = order;
+      for i in 0..5 {
+        buf[1 + i * 2..3 + i * 2].copy_from_slice(&chunk[i].to_le_bytes());
+      }
+      buf[11] = 0x0F; // LFN entries are flagged via the attribute byte
+      buf[13] = checksum;
+      for i in 0..6 {
+        buf[14 + i * 2..16 + i * 2].copy_from_slice(&chunk[5 + i].to_le_bytes());
+      }
+      for i in 0..2 {
+        buf[28 + i * 2..30 + i * 2].copy_from_slice(&chunk[11 + i].to_le_bytes());
+      }
+      buf
+    }).collect()
+  }
+
+  #[test]
+  fn classify_fat_type_follows_table_size_16_not_cluster_count() {
+    // Small enough that the old Microsoft cluster-count heuristic lands
+    // below 4085 clusters and would misclassify this as FAT12, even though
+    // table_size_16 == 0 means open_at() already committed to FAT32Ext.
+    let mut storage = formatted_image(16 * 1024 * 1024);
+
+    storage.seek(SeekFrom::Start(0)).unwrap();
+    let mut fat_header_buf = [0u8; FATHEADER_SIZE];
+    storage.read_exact(&mut fat_header_buf).unwrap();
+    let fat_header = FATHeader::from(fat_header_buf);
+    assert_eq!(fat_header.table_size_16, 0);
+
+    let mut fat32_ext_buf = [0u8; FAT32EXT_SIZE];
+    storage.read_exact(&mut fat32_ext_buf).unwrap();
+    let fat32_ext = FAT32Ext::from(fat32_ext_buf);
+
+    assert_eq!(classify_fat_type(&fat_header, fat32_ext.table_size_32), FatType::Fat32);
+  }
+
+  #[test]
+  fn root_dir_walks_the_cluster_chain_on_a_small_fat32_image() {
+    let mut storage = formatted_image(16 * 1024 * 1024);
+
+    // Hand-insert a short 8.3 dir entry for "HELLO.TXT" pointing at cluster 3,
+    // plus its file contents, directly into the image format_fat32() wrote.
+    let mut dir_entry = [0u8; 32];
+    dir_entry[0..11].copy_from_slice(b"HELLO   TXT");
+    dir_entry[26] = 3; // lowbits_cluster_number low byte => cluster 3
+    dir_entry[28] = 5; // filesize low byte => 5 bytes
+
+    let root_cluster_start = 49152; // data_start for this 16MiB layout (cluster 2)
+    storage.seek(SeekFrom::Start(root_cluster_start)).unwrap();
+    storage.write_all(&dir_entry).unwrap();
+
+    let file_cluster_start = root_cluster_start + 8 * 512; // cluster 3
+    storage.seek(SeekFrom::Start(file_cluster_start)).unwrap();
+    storage.write_all(b"hello").unwrap();
+
+    let mut volume = VolumeManager::open_at(storage, 0);
+    let entries = volume.root_dir();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name(), "HELLO.TXT");
+    assert_eq!(volume.read_file(&entries[0]), b"hello");
+  }
+
+  #[test]
+  fn read_file_returns_empty_for_a_zero_length_file() {
+    let mut storage = formatted_image(16 * 1024 * 1024);
+
+    // A zero-length file has no cluster allocated, so cluster stays 0 -
+    // this must short-circuit rather than underflow in cluster_offset().
+    let mut dir_entry = [0u8; 32];
+    dir_entry[0..11].copy_from_slice(b"EMPTY   TXT");
+
+    let root_cluster_start = 49152; // data_start for this 16MiB layout (cluster 2)
+    storage.seek(SeekFrom::Start(root_cluster_start)).unwrap();
+    storage.write_all(&dir_entry).unwrap();
+
+    let mut volume = VolumeManager::open_at(storage, 0);
+    let entries = volume.root_dir();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].cluster(), 0);
+    assert_eq!(volume.read_file(&entries[0]), Vec::<u8>::new());
+  }
+
+  #[test]
+  fn read_file_follows_a_multi_cluster_chain() {
+    let mut storage = formatted_image(16 * 1024 * 1024);
+
+    let mut dir_entry = [0u8; 32];
+    dir_entry[0..11].copy_from_slice(b"BIG     TXT");
+    dir_entry[26] = 3; // lowbits_cluster_number low byte => cluster 3
+    let cluster_size = 8 * 512;
+    let size = (cluster_size + 5) as u32;
+    dir_entry[28..32].copy_from_slice(&size.to_le_bytes());
+
+    let root_cluster_start = 49152; // data_start for this 16MiB layout (cluster 2)
+    storage.seek(SeekFrom::Start(root_cluster_start)).unwrap();
+    storage.write_all(&dir_entry).unwrap();
+
+    // Chain cluster 3 -> cluster 4 -> end of chain, so read_file has to
+    // actually follow FatValue::Next rather than stop after one cluster.
+    let fat_start = 16384;
+    storage.seek(SeekFrom::Start(fat_start + 3 * 4)).unwrap();
+    storage.write_all(&4u32.to_le_bytes()).unwrap();
+    storage.seek(SeekFrom::Start(fat_start + 4 * 4)).unwrap();
+    storage.write_all(&0x0FFFFFFFu32.to_le_bytes()).unwrap();
+
+    let cluster3_start = root_cluster_start + cluster_size as u64;
+    let cluster4_start = cluster3_start + cluster_size as u64;
+    storage.seek(SeekFrom::Start(cluster3_start)).unwrap();
+    storage.write_all(&vec![b'A'; cluster_size]).unwrap();
+    storage.seek(SeekFrom::Start(cluster4_start)).unwrap();
+    storage.write_all(b"hello").unwrap();
+
+    let mut volume = VolumeManager::open_at(storage, 0);
+    let entries = volume.root_dir();
+    let contents = volume.read_file(&entries[0]);
+
+    assert_eq!(contents.len(), cluster_size + 5);
+    assert!(contents[..cluster_size].iter().all(|&b| b == b'A'));
+    assert_eq!(&contents[cluster_size..], b"hello");
+  }
+
+  #[test]
+  fn dir_entry_name_decodes_a_multi_slot_utf16le_long_file_name() {
+    let long_name = "AVERYLONGFÀLENAME.TXT";
+    let short_name: [u8; 11] = *b"AVERYL~1TXT";
+    let checksum = dos_checksum(&short_name);
+    let slots = build_lfn_slots(long_name, checksum);
+    assert!(slots.len() >= 2, "name should require 2+ LFN slots");
+
+    let mut storage = formatted_image(16 * 1024 * 1024);
+    let root_cluster_start = 49152; // data_start for this 16MiB layout (cluster 2)
+    storage.seek(SeekFrom::Start(root_cluster_start)).unwrap();
+    // Real FAT stores slots highest-order-first; DirEntry::name() sorts by
+    // sequence number, so write them in the conventional reverse order too.
+    for slot in slots.iter().rev() {
+      storage.write_all(slot).unwrap();
+    }
+    let mut dir_entry = [0u8; 32];
+    dir_entry[0..11].copy_from_slice(&short_name);
+    storage.write_all(&dir_entry).unwrap();
+
+    let mut volume = VolumeManager::open_at(storage, 0);
+    let entries = volume.root_dir();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name(), long_name);
+  }
+
+  #[test]
+  fn dir_entry_name_falls_back_to_short_name_on_checksum_mismatch() {
+    let long_name = "AVERYLONGFÀLENAME.TXT";
+    let short_name: [u8; 11] = *b"AVERYL~1TXT";
+    let wrong_checksum = dos_checksum(&short_name).wrapping_add(1);
+    let slots = build_lfn_slots(long_name, wrong_checksum);
+
+    let mut storage = formatted_image(16 * 1024 * 1024);
+    let root_cluster_start = 49152;
+    storage.seek(SeekFrom::Start(root_cluster_start)).unwrap();
+    for slot in slots.iter().rev() {
+      storage.write_all(slot).unwrap();
+    }
+    let mut dir_entry = [0u8; 32];
+    dir_entry[0..11].copy_from_slice(&short_name);
+    storage.write_all(&dir_entry).unwrap();
+
+    let mut volume = VolumeManager::open_at(storage, 0);
+    let entries = volume.root_dir();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name(), "AVERYL~1.TXT");
+  }
+
+  #[test]
+  fn format_fat32_writes_a_well_formed_boot_sector_and_reserved_fat_entries() {
+    let size_bytes = 16 * 1024 * 1024;
+    let mut storage = formatted_image(size_bytes);
+
+    storage.seek(SeekFrom::Start(0)).unwrap();
+    let mut boot_sector = [0u8; 512];
+    storage.read_exact(&mut boot_sector).unwrap();
+    assert_eq!(boot_sector[510], 0x55);
+    assert_eq!(boot_sector[511], 0xAA);
+
+    let header_buf: [u8; FATHEADER_SIZE] = boot_sector[0..FATHEADER_SIZE].try_into().unwrap();
+    let fat_header = FATHeader::from(header_buf);
+    assert_eq!(fat_header.bootjmp, [0xEB, 0x58, 0x90]);
+    assert_eq!(fat_header.bytes_per_sector, 512);
+    assert_eq!(fat_header.sectors_per_cluster, 8);
+    assert_eq!(fat_header.reserved_sector_count, 32);
+    assert_eq!(fat_header.table_count, 2);
+    assert_eq!(fat_header.media_type, 0xF8);
+    assert_eq!(fat_header.table_size_16, 0);
+    assert_eq!(fat_header.total_sectors_32, (size_bytes / 512) as u32);
+
+    let ext_buf: [u8; FAT32EXT_SIZE] =
+      boot_sector[FATHEADER_SIZE..FATHEADER_SIZE + FAT32EXT_SIZE].try_into().unwrap();
+    let fat32_ext = FAT32Ext::from(ext_buf);
+    assert_eq!(fat32_ext.root_cluster, 2);
+    assert_eq!(fat32_ext.fat_info, 1);
+    assert_eq!(fat32_ext.backup_bs_sector, 6);
+    assert_eq!(fat32_ext.boot_signature, 0x29);
+    assert_eq!(&fat32_ext.fat_type_label, b"FAT32   ");
+    assert_eq!(&fat32_ext.volume_label, b"NO NAME    ");
+
+    // The backup boot sector (sector 6) should be an exact copy of sector 0.
+    storage.seek(SeekFrom::Start((fat32_ext.backup_bs_sector as u64) * 512)).unwrap();
+    let mut backup_boot_sector = [0u8; 512];
+    storage.read_exact(&mut backup_boot_sector).unwrap();
+    assert_eq!(backup_boot_sector, boot_sector);
+
+    // FSInfo sector (sector 1): lead/struct/trail signatures.
+    storage.seek(SeekFrom::Start((fat32_ext.fat_info as u64) * 512)).unwrap();
+    let mut fs_info = [0u8; 512];
+    storage.read_exact(&mut fs_info).unwrap();
+    assert_eq!(u32::from_le_bytes(fs_info[0..4].try_into().unwrap()), 0x41615252);
+    assert_eq!(u32::from_le_bytes(fs_info[484..488].try_into().unwrap()), 0x61417272);
+    assert_eq!(u32::from_le_bytes(fs_info[508..512].try_into().unwrap()), 0xAA550000);
+
+    // Reserved FAT entries: cluster 0 and 1 are media-type/EOC markers, and
+    // cluster 2 (the root directory's only cluster) is end-of-chain.
+    let fat_start = (fat_header.reserved_sector_count as u64) * (fat_header.bytes_per_sector as u64);
+    storage.seek(SeekFrom::Start(fat_start)).unwrap();
+    let mut first_three_entries = [0u8; 12];
+    storage.read_exact(&mut first_three_entries).unwrap();
+    assert_eq!(u32::from_le_bytes(first_three_entries[0..4].try_into().unwrap()) & 0x0FFFFFFF, 0x0FFFFFF8);
+    assert_eq!(u32::from_le_bytes(first_three_entries[4..8].try_into().unwrap()) & 0x0FFFFFFF, 0x0FFFFFFF);
+    assert_eq!(u32::from_le_bytes(first_three_entries[8..12].try_into().unwrap()) & 0x0FFFFFFF, 0x0FFFFFFF);
+  }
+
+  #[test]
+  fn read_mbr_returns_none_for_a_bare_fat_volume() {
+    let mut storage = formatted_image(16 * 1024 * 1024);
+    assert!(read_mbr(&mut storage).is_none());
+  }
+
+  #[test]
+  fn read_mbr_parses_a_real_partition_table() {
+    let mut sector = [0u8; 512];
+    let entry_offset = 446;
+    sector[entry_offset] = 0x80; // bootable
+    sector[entry_offset + 4] = 0x0C; // FAT32 LBA partition type
+    sector[entry_offset + 8..entry_offset + 12].copy_from_slice(&2048u32.to_le_bytes());
+    sector[entry_offset + 12..entry_offset + 16].copy_from_slice(&204800u32.to_le_bytes());
+    sector[510] = 0x55;
+    sector[511] = 0xAA;
+
+    let mut storage = Cursor::new(sector.to_vec());
+    let mbr = read_mbr(&mut storage).expect("a real partition table should parse as an MBR");
+    assert_eq!(select_partition_offset(&mbr, 0), 2048 * 512);
+  }
+}